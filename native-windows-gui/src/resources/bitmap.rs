@@ -1,21 +1,24 @@
+use winapi::shared::windef::HWND;
 use winapi::um::winnt::HANDLE;
 use winapi::um::winuser::IMAGE_BITMAP;
 use crate::win32::resources_helper as rh;
 use crate::{OemBitmap, OemImage, NwgError};
 use std::ptr;
+use std::mem;
+use std::cell::Cell;
 
 
-/** 
+/**
 A wrapper over a bitmap file (*.bmp)
 
-Note that Bitmap object are only used as display resources (ie: it's impossible to read pixels or resized it).
-If those features are needed, see the `image-decoder` feature.
-
 To display a bitmap in an application, see the `ImageFrame` control.
 
 By default, bitmap resources do not support transparency BUT if `image-decoder` is enabled, bitmaps can be loaded
 from any file type supported by NWG (JPEG, PNG, BMP, ICO, DDS, TIFF).
 
+When `image-decoder` is enabled, `BitmapBuilder::scale_mode` can also be used to control how the image is
+resized to fit `size` (see `ScaleMode`), using WIC's high quality bitmap scaler instead of a plain GDI stretch.
+
 **Builder parameters:**
   * `parent`:   **Required.** The button parent container.
   * `text`:     The button text.
@@ -49,7 +52,39 @@ fn load_bitmap() -> nwg::Bitmap {
 #[allow(unused)]
 pub struct Bitmap {
     pub handle: HANDLE,
-    pub(crate) owned: bool
+    pub(crate) owned: bool,
+    pub(crate) has_alpha: bool,
+    source: Option<LazyBitmapSource>,
+    cached_handle: Cell<HANDLE>,
+}
+
+/// The source kept around by a bitmap built with `BitmapBuilder::lazy(true)`.
+#[derive(PartialEq)]
+struct LazyBitmapSource {
+    data: BitmapSource,
+    size: (u32, u32),
+    scale_mode: ScaleMode,
+    transparency_key: Option<[u8; 3]>,
+    alpha_channel: bool,
+}
+
+#[derive(PartialEq)]
+enum BitmapSource {
+    File(String),
+    Memory(Vec<u8>),
+}
+
+/// Describes how a `Bitmap` is resized to fit the target `size` passed to [`BitmapBuilder::size`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ScaleMode {
+    /// Stretch the image to exactly match the target size, ignoring its aspect ratio.
+    Stretch,
+    /// Scale the image so it fits entirely within the target size, preserving its aspect ratio.
+    Fit,
+    /// Scale the image so it fully covers the target size, preserving its aspect ratio, and crop the centered excess.
+    Fill,
+    /// Scale and crop the image to a centered square matching the smallest target dimension.
+    CropToSquare,
 }
 
 impl Bitmap {
@@ -59,21 +94,297 @@ impl Bitmap {
             source_text: None,
             source_bin: None,
             source_system: None,
+            source_executable: None,
             transparency_key: None,
             size: None,
-            strict: false
+            scale_mode: ScaleMode::Stretch,
+            strict: false,
+            lazy: false,
+            alpha_channel: false,
+        }
+    }
+
+    /// Returns the width and height, in pixels, of the bitmap.
+    ///
+    /// For a bitmap built with `BitmapBuilder::lazy(true)`, this returns the dimensions decoded
+    /// at build time without needing to materialize the underlying GDI handle.
+    pub fn size(&self) -> (u32, u32) {
+        if let Some(lazy) = self.source.as_ref() {
+            return lazy.size;
+        }
+
+        use winapi::um::wingdi::{BITMAP, GetObjectW};
+
+        let mut bmp: BITMAP = unsafe { mem::zeroed() };
+        let bmp_ptr = &mut bmp as *mut BITMAP as *mut _;
+        let size = mem::size_of::<BITMAP>() as i32;
+
+        unsafe { GetObjectW(self.handle as _, size, bmp_ptr); }
+
+        (bmp.bmWidth as u32, bmp.bmHeight as u32)
+    }
+
+    /// Returns `true` if the bitmap's pixels carry a premultiplied alpha channel that should be drawn
+    /// with `AlphaBlend`/`AC_SRC_ALPHA` rather than `BitBlt`, ie: it was created through
+    /// `Bitmap::create_premultiplied`, or built with `BitmapBuilder::alpha_channel(true)` where that
+    /// setting takes effect (see `BitmapBuilder::alpha_channel`).
+    pub fn has_alpha(&self) -> bool {
+        self.has_alpha
+    }
+
+    /// Reads back the bitmap pixels as a top-down, straight-alpha 32bit RGBA buffer.
+    ///
+    /// This allows a `Bitmap` to be round-tripped to the `image` crate or serialized without
+    /// requiring the `image-decoder` feature. For a lazy bitmap, call `ensure_handle` first.
+    pub fn to_rgba(&self) -> Result<Vec<u8>, NwgError> {
+        use winapi::um::wingdi::{
+            BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS, GetDIBits,
+            CreateCompatibleDC, DeleteDC
+        };
+        use winapi::um::winuser::{GetDesktopWindow, GetDC, ReleaseDC};
+
+        let handle = self.current_handle();
+        let (w, h) = self.size();
+
+        let mut bmi: BITMAPINFO = unsafe { mem::zeroed() };
+        bmi.bmiHeader = BITMAPINFOHEADER {
+            biSize: mem::size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: w as i32,
+            biHeight: -(h as i32),
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB,
+            biSizeImage: 0,
+            biXPelsPerMeter: 0,
+            biYPelsPerMeter: 0,
+            biClrUsed: 0,
+            biClrImportant: 0,
+        };
+
+        let mut pixels: Vec<u8> = vec![0; (w * h * 4) as usize];
+
+        unsafe {
+            let screen_dc = GetDC(GetDesktopWindow());
+            let dc = CreateCompatibleDC(screen_dc);
+
+            // `handle` must not be selected into any DC while `GetDIBits` reads it, so read
+            // directly off the freshly created compatible DC without selecting it first.
+            let result = GetDIBits(dc, handle as _, 0, h, pixels.as_mut_ptr() as _, &mut bmi, DIB_RGB_COLORS);
+
+            DeleteDC(dc);
+            ReleaseDC(GetDesktopWindow(), screen_dc);
+
+            if result == 0 {
+                return Err(NwgError::resource_create("Failed to read bitmap pixels"));
+            }
+        }
+
+        // GDI returns 32bit pixels as BGRA, swap to RGBA
+        for px in pixels.chunks_exact_mut(4) {
+            px.swap(0, 2);
+        }
+
+        // `has_alpha` bitmaps are stored premultiplied; un-premultiply so consumers (e.g. the
+        // `image` crate) that expect straight alpha don't see darkened colors.
+        if self.has_alpha {
+            for px in pixels.chunks_exact_mut(4) {
+                let a = px[3];
+                if a != 0 {
+                    px[0] = (px[0] as u32 * 255 / a as u32) as u8;
+                    px[1] = (px[1] as u32 * 255 / a as u32) as u8;
+                    px[2] = (px[2] as u32 * 255 / a as u32) as u8;
+                }
+            }
+        }
+
+        Ok(pixels)
+    }
+
+    /// Materializes the GDI handle for a bitmap built with `BitmapBuilder::lazy(true)`, decoding
+    /// the stored source on first call and returning the cached handle on subsequent calls.
+    ///
+    /// For a bitmap that wasn't built as lazy, this simply returns `self.handle`.
+    pub fn ensure_handle(&self) -> Result<HANDLE, NwgError> {
+        if !self.handle.is_null() {
+            return Ok(self.handle);
+        }
+
+        let cached = self.cached_handle.get();
+        if !cached.is_null() {
+            return Ok(cached);
+        }
+
+        let lazy = match self.source.as_ref() {
+            Some(v) => v,
+            None => return Err(NwgError::resource_create("Bitmap has no source to decode")),
+        };
+
+        let mut handle = unsafe { rh::bitmap_from_source(&lazy.data, lazy.size, lazy.scale_mode, lazy.alpha_channel)? };
+
+        if let Some(key) = lazy.transparency_key {
+            let size = (lazy.size.0 as i32, lazy.size.1 as i32);
+            handle = unsafe { rh::make_bitmap_transparent(handle, size, key)? };
+        }
+
+        self.cached_handle.set(handle);
+
+        Ok(handle)
+    }
+
+    /// Returns the GDI handle to use for drawing, resolving through `cached_handle` for a
+    /// `lazy(true)` bitmap that was already materialized via `ensure_handle`. Returns null if the
+    /// bitmap is lazy and hasn't been materialized yet.
+    fn current_handle(&self) -> HANDLE {
+        if !self.handle.is_null() {
+            self.handle
+        } else {
+            self.cached_handle.get()
+        }
+    }
+
+    /// Drops the GDI handle materialized by `ensure_handle` while keeping the compressed source
+    /// data, so the bitmap can be re-hydrated on demand later. Does nothing for a non-lazy bitmap.
+    pub fn release(&self) {
+        use winapi::um::wingdi::DeleteObject;
+
+        let cached = self.cached_handle.get();
+        if !cached.is_null() {
+            unsafe { DeleteObject(cached); }
+            self.cached_handle.set(ptr::null_mut());
         }
     }
 
+    /// Creates a blank, top-down, 32bpp premultiplied-alpha DIB section sized `size`.
+    ///
+    /// DWM rejects plain device-dependent bitmaps for iconic thumbnails and live previews, so
+    /// render the desired content into this bitmap (e.g. through a memory DC selecting `handle`)
+    /// before passing it to `set_iconic_thumbnail` or `set_iconic_live_preview`.
+    pub fn create_premultiplied(size: (u32, u32)) -> Result<Bitmap, NwgError> {
+        use winapi::um::wingdi::{BITMAPINFO, BITMAPINFOHEADER, BI_RGB, CreateDIBSection, DIB_RGB_COLORS};
+        use winapi::um::winuser::{GetDesktopWindow, GetDC, ReleaseDC};
+
+        let (w, h) = size;
+
+        let mut bmi: BITMAPINFO = unsafe { mem::zeroed() };
+        bmi.bmiHeader = BITMAPINFOHEADER {
+            biSize: mem::size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: w as i32,
+            biHeight: -(h as i32),
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB,
+            biSizeImage: 0,
+            biXPelsPerMeter: 0,
+            biYPelsPerMeter: 0,
+            biClrUsed: 0,
+            biClrImportant: 0,
+        };
+
+        let handle = unsafe {
+            let screen_dc = GetDC(GetDesktopWindow());
+            let mut bits = ptr::null_mut();
+            let handle = CreateDIBSection(screen_dc, &bmi, DIB_RGB_COLORS, &mut bits, ptr::null_mut(), 0);
+            ReleaseDC(GetDesktopWindow(), screen_dc);
+            handle
+        };
+
+        if handle.is_null() {
+            return Err(NwgError::resource_create("Failed to create a DIB section for the bitmap"));
+        }
+
+        Ok(Bitmap {
+            handle: handle as HANDLE,
+            owned: true,
+            has_alpha: true,
+            source: None,
+            cached_handle: Cell::new(ptr::null_mut()),
+        })
+    }
+
+    /// Registers this bitmap as the taskbar/Aero-peek iconic thumbnail of `hwnd`.
+    ///
+    /// `handle` must be a premultiplied-alpha DIB section, see `Bitmap::create_premultiplied`.
+    /// This also turns on `DWMWA_HAS_ICONIC_BITMAP`/`DWMWA_FORCE_ICONIC_REPRESENTATION` on `hwnd`,
+    /// which DWM requires before it will ever ask for or use a custom iconic thumbnail.
+    pub fn set_iconic_thumbnail(&self, hwnd: HWND) -> Result<(), NwgError> {
+        use winapi::um::dwmapi::DwmSetIconicThumbnail;
+
+        enable_iconic_bitmaps(hwnd)?;
+
+        let result = unsafe { DwmSetIconicThumbnail(hwnd, self.current_handle() as _, 0) };
+        if result != 0 {
+            return Err(NwgError::resource_create("Failed to set the iconic thumbnail"));
+        }
+
+        Ok(())
+    }
+
+    /// Registers this bitmap as the Aero-peek live preview bitmap of `hwnd`.
+    ///
+    /// `handle` must be a premultiplied-alpha DIB section, see `Bitmap::create_premultiplied`.
+    /// This also turns on `DWMWA_HAS_ICONIC_BITMAP`/`DWMWA_FORCE_ICONIC_REPRESENTATION` on `hwnd`,
+    /// which DWM requires before it will ever ask for or use a custom live preview bitmap.
+    pub fn set_iconic_live_preview(&self, hwnd: HWND) -> Result<(), NwgError> {
+        use winapi::um::dwmapi::DwmSetIconicLivePreviewBitmap;
+
+        enable_iconic_bitmaps(hwnd)?;
+
+        let result = unsafe { DwmSetIconicLivePreviewBitmap(hwnd, self.current_handle() as _, ptr::null_mut(), 0) };
+        if result != 0 {
+            return Err(NwgError::resource_create("Failed to set the iconic live preview bitmap"));
+        }
+
+        Ok(())
+    }
+
+}
+
+/// Turns on `DWMWA_HAS_ICONIC_BITMAP` and `DWMWA_FORCE_ICONIC_REPRESENTATION` on `hwnd`. DWM only
+/// ever requests a custom iconic thumbnail/live preview once both are set, so `set_iconic_thumbnail`
+/// and `set_iconic_live_preview` call this before handing over the bitmap.
+fn enable_iconic_bitmaps(hwnd: HWND) -> Result<(), NwgError> {
+    use winapi::um::dwmapi::{DwmSetWindowAttribute, DWMWA_HAS_ICONIC_BITMAP, DWMWA_FORCE_ICONIC_REPRESENTATION};
+    use winapi::shared::minwindef::{BOOL, TRUE};
+
+    let enabled: BOOL = TRUE;
+    let size = mem::size_of::<BOOL>() as u32;
+
+    unsafe {
+        let r1 = DwmSetWindowAttribute(hwnd, DWMWA_FORCE_ICONIC_REPRESENTATION, &enabled as *const _ as _, size);
+        let r2 = DwmSetWindowAttribute(hwnd, DWMWA_HAS_ICONIC_BITMAP, &enabled as *const _ as _, size);
+
+        if r1 != 0 || r2 != 0 {
+            return Err(NwgError::resource_create("Failed to enable iconic bitmap support on the window"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Tells DWM that the iconic thumbnail and live preview bitmaps of `hwnd` are stale, so it should
+/// request new ones from the application instead of reusing the cached images.
+pub fn invalidate_iconic_bitmaps(hwnd: HWND) -> Result<(), NwgError> {
+    use winapi::um::dwmapi::DwmInvalidateIconicBitmaps;
+
+    let result = unsafe { DwmInvalidateIconicBitmaps(hwnd) };
+    if result != 0 {
+        return Err(NwgError::resource_create("Failed to invalidate the iconic bitmaps"));
+    }
+
+    Ok(())
 }
 
 pub struct BitmapBuilder<'a> {
     source_text: Option<&'a str>,
     source_bin: Option<&'a [u8]>,
     source_system: Option<OemBitmap>,
+    source_executable: Option<(&'a str, u32)>,
     transparency_key: Option<[u8; 3]>,
     size: Option<(u32, u32)>,
+    scale_mode: ScaleMode,
     strict: bool,
+    lazy: bool,
+    alpha_channel: bool,
 }
 
 impl<'a> BitmapBuilder<'a> {
@@ -93,11 +404,32 @@ impl<'a> BitmapBuilder<'a> {
         self
     }
 
+    /// Loads the large icon of an executable or DLL as the bitmap, given its path and icon index.
+    ///
+    /// Internally this extracts the icon with `ExtractIconExW` and converts it to a full 32bpp
+    /// device-independent bitmap, so the result isn't silently downgraded to a 16x16 monochrome image.
+    pub fn source_executable(mut self, t: Option<(&'a str, u32)>) -> BitmapBuilder<'a> {
+        self.source_executable = t;
+        self
+    }
+
     pub fn size(mut self, s: Option<(u32, u32)>) -> BitmapBuilder<'a> {
         self.size = s;
         self
     }
 
+    /// Controls how the image is resized to fit `size`. Defaults to `ScaleMode::Stretch`.
+    /// Only applies to `source_file`/`source_bin`; `source_system` and `source_executable` ignore it.
+    ///
+    /// `Fit`, `Fill` and `CropToSquare` require the `image-decoder` feature: they go through
+    /// WIC's high quality bitmap scaler instead of GDI's `StretchBlt`, which avoids the jagged
+    /// look of the default resize when displaying photos in an `ImageFrame`. Without the
+    /// `image-decoder` feature, the image is always stretched regardless of this setting.
+    pub fn scale_mode(mut self, s: ScaleMode) -> BitmapBuilder<'a> {
+        self.scale_mode = s;
+        self
+    }
+
     pub fn strict(mut self, s: bool) -> BitmapBuilder<'a> {
         self.strict = s;
         self
@@ -108,23 +440,89 @@ impl<'a> BitmapBuilder<'a> {
         self
     }
 
+    /// When set to `true`, the bitmap only stores its compressed `source_file`/`source_bin` data
+    /// plus its decoded size, and defers creating the GDI handle to the first `Bitmap::ensure_handle`
+    /// call. This keeps memory usage low for apps that build hundreds of `Bitmap` resources (e.g.
+    /// galleries or thumbnail grids). Only `source_file` and `source_bin` support laziness.
+    pub fn lazy(mut self, l: bool) -> BitmapBuilder<'a> {
+        self.lazy = l;
+        self
+    }
+
+    /// When set to `true` and loading through the `image-decoder` feature, preserves the source's
+    /// 32bpp BGRA data with premultiplied alpha instead of flattening it to an opaque bitmap. Use
+    /// `Bitmap::has_alpha` to tell callers to draw the result with `AlphaBlend` instead of `BitBlt`.
+    /// PNG/TIFF images with soft or transparent edges then display correctly over any background.
+    /// Only applies to `source_file`/`source_bin`; `source_system` and `source_executable` ignore it.
+    ///
+    /// This is ignored (the resulting bitmap never reports `has_alpha`) without the `image-decoder`
+    /// feature, and is mutually exclusive with `transparency_key`, which always wins if both are set.
+    pub fn alpha_channel(mut self, a: bool) -> BitmapBuilder<'a> {
+        self.alpha_channel = a;
+        self
+    }
+
     pub fn build(self, b: &mut Bitmap) -> Result<(), NwgError> {
+        if self.lazy {
+            let data = if let Some(src) = self.source_text {
+                BitmapSource::File(src.to_string())
+            } else if let Some(src) = self.source_bin {
+                BitmapSource::Memory(src.to_vec())
+            } else {
+                return Err(NwgError::resource_create("Lazy bitmaps can only be built from `source_file` or `source_bin`"));
+            };
+
+            let size = unsafe { rh::bitmap_source_size(&data, self.size)? };
+
+            // Alpha is only ever preserved when the source is actually decoded through WIC, and
+            // color-key transparency (applied once materialized, see `ensure_handle`) flattens it.
+            let alpha_channel = self.alpha_channel && cfg!(feature = "image-decoder") && self.transparency_key.is_none();
+
+            *b = Bitmap {
+                handle: ptr::null_mut(),
+                owned: false,
+                has_alpha: alpha_channel,
+                source: Some(LazyBitmapSource {
+                    data,
+                    size,
+                    scale_mode: self.scale_mode,
+                    transparency_key: self.transparency_key,
+                    alpha_channel,
+                }),
+                cached_handle: Cell::new(ptr::null_mut()),
+            };
+
+            return Ok(());
+        }
+
         let mut handle;
-        
+        let mut has_alpha = false;
+
         if let Some(src) = self.source_text {
-            handle = unsafe { 
+            handle = unsafe {
                 #[cfg(feature="image-decoder")]
-                let handle = rh::build_image_decoder(src, self.size, self.strict, IMAGE_BITMAP);
+                let handle = rh::build_image_decoder(src, self.size, self.scale_mode, self.alpha_channel, self.strict, IMAGE_BITMAP);
 
                 #[cfg(not(feature="image-decoder"))]
                 let handle = rh::build_image(src, self.size, self.strict, IMAGE_BITMAP);
 
                 handle
             };
+
+            // Only the `image-decoder` (WIC) path is able to preserve a real alpha channel.
+            has_alpha = self.alpha_channel && cfg!(feature = "image-decoder");
         } else if let Some(src) = self.source_system {
             handle = unsafe { rh::build_oem_image(OemImage::Bitmap(src), self.size) };
-        } else if let Some(src) = self.source_bin { 
-            handle = unsafe { rh::bitmap_from_memory(src) };
+        } else if let Some(src) = self.source_bin {
+            let data = BitmapSource::Memory(src.to_vec());
+            let size = unsafe { rh::bitmap_source_size(&data, self.size)? };
+
+            // Only the `image-decoder` (WIC) path is able to preserve a real alpha channel.
+            has_alpha = self.alpha_channel && cfg!(feature = "image-decoder");
+
+            handle = unsafe { rh::bitmap_from_source(&data, size, self.scale_mode, has_alpha) };
+        } else if let Some((path, index)) = self.source_executable {
+            handle = unsafe { rh::bitmap_from_executable(path, index) };
         } else {
             return Err(NwgError::resource_create("No source provided for Bitmap"));
         }
@@ -135,11 +533,19 @@ impl<'a> BitmapBuilder<'a> {
                 None => (0, 0)
             };
 
+            // Color-key transparency flattens the image, so it can't carry a real alpha channel.
+            has_alpha = false;
             handle = unsafe { rh::make_bitmap_transparent(handle?, size, key) };
         }
-        
-        *b = Bitmap { handle: handle?, owned: true };
-    
+
+        *b = Bitmap {
+            handle: handle?,
+            owned: true,
+            has_alpha,
+            source: None,
+            cached_handle: Cell::new(ptr::null_mut()),
+        };
+
         Ok(())
     }
 
@@ -151,7 +557,10 @@ impl Default for Bitmap {
     fn default() -> Bitmap {
         Bitmap {
             handle: ptr::null_mut(),
-            owned: false
+            owned: false,
+            has_alpha: false,
+            source: None,
+            cached_handle: Cell::new(ptr::null_mut()),
         }
     }
 
@@ -160,7 +569,11 @@ impl Default for Bitmap {
 impl PartialEq for Bitmap {
 
     fn eq(&self, other: &Self) -> bool {
-        self.handle == other.handle
+        match (self.source.as_ref(), other.source.as_ref()) {
+            (Some(a), Some(b)) => a == b,
+            (None, None) => self.handle == other.handle,
+            _ => false,
+        }
     }
 
 }
@@ -172,6 +585,8 @@ impl Drop for Bitmap {
         if self.owned {
             unsafe { DeleteObject(self.handle); }
         }
+
+        self.release();
     }
 
 }
@@ -0,0 +1,148 @@
+use winapi::um::winnt::HANDLE;
+use winapi::um::winuser::IMAGE_ICON;
+use crate::win32::resources_helper as rh;
+use crate::{OemIcon, OemImage, NwgError};
+use std::ptr;
+
+
+/**
+A wrapper over a icon file (*.ico)
+
+To display an icon in an application, see the `ImageFrame` control.
+
+**Builder parameters:**
+  * `source_file`:       The source of the icon as a file path. Either this value or `source_bin` or `source_system` or `source_executable` must be set.
+  * `source_bin`:        The source of the icon as a byte slice. Either this value or `source_file` or `source_system` or `source_executable` must be set.
+  * `source_system`:     The source of the icon as a system resource. Either this value or `source_file` or `source_bin` or `source_executable` must be set.
+  * `source_executable`: The source of the icon as the large icon of an executable or DLL, given its path and icon index.
+  * `strict`:            If true, the builder will panic if the icon cannot be loaded instead of silently ignoring the error
+
+Example:
+
+```rust
+use native_windows_gui as nwg;
+
+fn load_icon() -> nwg::Icon {
+    let mut icon = nwg::Icon::default();
+
+    nwg::Icon::builder()
+        .source_file(Some("Hello.ico"))
+        .strict(true)
+        .build(&mut icon);
+
+    icon
+}
+
+```
+
+*/
+#[allow(unused)]
+pub struct Icon {
+    pub handle: HANDLE,
+    pub(crate) owned: bool
+}
+
+impl Icon {
+
+    pub fn builder<'a>() -> IconBuilder<'a> {
+        IconBuilder {
+            source_text: None,
+            source_bin: None,
+            source_system: None,
+            source_executable: None,
+            strict: false
+        }
+    }
+
+}
+
+pub struct IconBuilder<'a> {
+    source_text: Option<&'a str>,
+    source_bin: Option<&'a [u8]>,
+    source_system: Option<OemIcon>,
+    source_executable: Option<(&'a str, u32)>,
+    strict: bool,
+}
+
+impl<'a> IconBuilder<'a> {
+
+    pub fn source_file(mut self, t: Option<&'a str>) -> IconBuilder<'a> {
+        self.source_text = t;
+        self
+    }
+
+    pub fn source_bin(mut self, t: Option<&'a [u8]>) -> IconBuilder<'a> {
+        self.source_bin = t;
+        self
+    }
+
+    pub fn source_system(mut self, t: Option<OemIcon>) -> IconBuilder<'a> {
+        self.source_system = t;
+        self
+    }
+
+    /// Loads the large icon of an executable or DLL, given its path and icon index.
+    ///
+    /// Internally this extracts the large icon with `ExtractIconExW`; the result is kept as an icon handle.
+    pub fn source_executable(mut self, t: Option<(&'a str, u32)>) -> IconBuilder<'a> {
+        self.source_executable = t;
+        self
+    }
+
+    pub fn strict(mut self, s: bool) -> IconBuilder<'a> {
+        self.strict = s;
+        self
+    }
+
+    pub fn build(self, i: &mut Icon) -> Result<(), NwgError> {
+        let handle;
+
+        if let Some(src) = self.source_text {
+            handle = unsafe { rh::build_image(src, None, self.strict, IMAGE_ICON) };
+        } else if let Some(src) = self.source_system {
+            handle = unsafe { rh::build_oem_image(OemImage::Icon(src), None) };
+        } else if let Some(src) = self.source_bin {
+            handle = unsafe { rh::icon_from_memory(src) };
+        } else if let Some((path, index)) = self.source_executable {
+            handle = unsafe { rh::icon_from_executable(path, index) };
+        } else {
+            return Err(NwgError::resource_create("No source provided for Icon"));
+        }
+
+        *i = Icon { handle: handle?, owned: true };
+
+        Ok(())
+    }
+
+}
+
+
+impl Default for Icon {
+
+    fn default() -> Icon {
+        Icon {
+            handle: ptr::null_mut(),
+            owned: false
+        }
+    }
+
+}
+
+impl PartialEq for Icon {
+
+    fn eq(&self, other: &Self) -> bool {
+        self.handle == other.handle
+    }
+
+}
+
+impl Drop for Icon {
+
+    fn drop(&mut self) {
+        use winapi::um::winuser::DestroyIcon;
+        if self.owned {
+            unsafe { DestroyIcon(self.handle as _); }
+        }
+    }
+
+}